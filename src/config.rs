@@ -0,0 +1,86 @@
+use chrono::{DateTime, Utc};
+
+/// Parsed command line configuration for a single run of the client.
+pub struct Config {
+    pub room_id: u64,
+    pub sessdata: Option<String>,
+    pub uid: Option<u64>,
+    /// `--irc <addr>`: when set, mirror the room into a local IRC server
+    /// (e.g. `127.0.0.1:6667`) so any IRC client can follow along.
+    pub irc_addr: Option<String>,
+    /// `--serve <addr>`: when set, expose the room as a browser overlay
+    /// (e.g. `127.0.0.1:8080`) for embedding in OBS.
+    pub serve_addr: Option<String>,
+    /// `--archive <path>`: when set, persist every danmaku, gift, superchat,
+    /// guard purchase, interaction and live start/stop event to a SQLite
+    /// database at this path. Welcome, warning and cut-off notices are
+    /// shown but not archived.
+    pub archive_path: Option<String>,
+    /// `--since <rfc3339>`: switches into history-replay mode instead of
+    /// connecting, printing archived messages for `room_id` since this time.
+    pub replay_since: Option<DateTime<Utc>>,
+    /// `--limit <n>`: caps how many replayed rows are printed.
+    pub replay_limit: Option<u64>,
+    /// `--metrics-addr <addr>`: when set, expose Prometheus metrics
+    /// (e.g. `127.0.0.1:9898`) on a `/metrics` endpoint.
+    pub metrics_addr: Option<String>,
+}
+
+impl Config {
+    /// Parses the raw `env::args()` vector (including the program name at
+    /// index 0) into a `Config`. The bare room id is accepted as the first
+    /// positional argument; everything else is a named flag.
+    pub fn from_args(args: Vec<String>) -> Config {
+        let mut room_id = None;
+        let mut sessdata = None;
+        let mut uid = None;
+        let mut irc_addr = None;
+        let mut serve_addr = None;
+        let mut archive_path = None;
+        let mut replay_since = None;
+        let mut replay_limit = None;
+        let mut metrics_addr = None;
+
+        let mut iter = args.into_iter().skip(1);
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--sessdata" => sessdata = iter.next(),
+                "--uid" => uid = iter.next().and_then(|v| v.parse().ok()),
+                "--irc" => {
+                    irc_addr = Some(iter.next().unwrap_or_else(|| "127.0.0.1:6667".to_string()));
+                }
+                "--serve" => {
+                    serve_addr = Some(iter.next().unwrap_or_else(|| "127.0.0.1:8080".to_string()));
+                }
+                "--archive" => archive_path = iter.next(),
+                "--since" => {
+                    replay_since = iter
+                        .next()
+                        .and_then(|v| DateTime::parse_from_rfc3339(&v).ok())
+                        .map(|v| v.with_timezone(&Utc));
+                }
+                "--limit" => replay_limit = iter.next().and_then(|v| v.parse().ok()),
+                "--metrics-addr" => {
+                    metrics_addr = Some(iter.next().unwrap_or_else(|| "127.0.0.1:9898".to_string()));
+                }
+                other => {
+                    if room_id.is_none() {
+                        room_id = other.parse().ok();
+                    }
+                }
+            }
+        }
+
+        Config {
+            room_id: room_id.expect("Missing required room id argument"),
+            sessdata,
+            uid,
+            irc_addr,
+            serve_addr,
+            archive_path,
+            replay_since,
+            replay_limit,
+            metrics_addr,
+        }
+    }
+}
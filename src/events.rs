@@ -0,0 +1,111 @@
+use std::sync::{
+    mpsc::{self, Receiver, Sender},
+    Mutex,
+};
+
+use colored::{ColoredString, Colorize};
+use serde::{Deserialize, Serialize};
+
+use crate::message::{guard::GuardLevel, interact::InteractType};
+
+/// A single danmaku-room event, stripped down to the fields a projection
+/// (IRC bridge, browser overlay, ...) actually needs to render it. Built
+/// from a `LiveMessage` once in `process_live_message`, alongside the
+/// existing terminal `println!`. The `type` tag matches the event names
+/// browser overlays key their CSS off of (`danmaku`, `gift`, ...).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProjectionEvent {
+    Danmaku {
+        uid: u64,
+        username: String,
+        guard_level: Option<GuardLevel>,
+        badge_name: Option<String>,
+        badge_level: Option<u64>,
+        text: String,
+    },
+    #[serde(rename = "gift")]
+    SendGift {
+        uid: u64,
+        username: String,
+        gift_name: String,
+        count: u64,
+    },
+    #[serde(rename = "superchat")]
+    SuperChat {
+        uid: u64,
+        username: String,
+        price: f64,
+        message: String,
+    },
+    #[serde(rename = "guard")]
+    GuardBuy {
+        uid: u64,
+        username: String,
+        guard_level: GuardLevel,
+        count: u64,
+    },
+    Interact {
+        uid: u64,
+        username: String,
+        interact_type: InteractType,
+    },
+    LiveStart,
+    LiveStop,
+}
+
+/// Colors a sender's name by guard level, the shared convention used by both
+/// the live terminal renderer and the archive replay view.
+pub fn colored_name(name: &str, guard_level: Option<GuardLevel>) -> ColoredString {
+    match guard_level {
+        None => name.bright_green(),
+        Some(GuardLevel::Captain) => name.bright_blue(),
+        Some(GuardLevel::Commander) => name.bright_purple(),
+        Some(GuardLevel::Governor) => name.bright_yellow(),
+    }
+}
+
+/// Colors a medal badge name by its level, the shared convention used by
+/// both the live terminal renderer and the archive replay view.
+pub fn colored_badge_name(name: &str, badge_level: u64) -> ColoredString {
+    match badge_level {
+        (1..=4) => name.green(),
+        (5..=8) => name.blue(),
+        (9..=12) => name.magenta(),
+        (13..=16) => name.red(),
+        (17..=20) => name.yellow(),
+        (21..=24) => name.bright_green(),
+        (25..=28) => name.bright_blue(),
+        (29..=32) => name.bright_magenta(),
+        (33..=36) => name.bright_red(),
+        (37..=40) => name.bright_yellow(),
+        _ => name.clear(),
+    }
+}
+
+/// Fan-out hub: every `publish` is delivered to every live subscriber.
+/// Subscribers that have hung up are pruned lazily on the next publish.
+pub struct EventBus {
+    subscribers: Mutex<Vec<Sender<ProjectionEvent>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self {
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Registers a new subscriber and returns the receiving half of its channel.
+    pub fn subscribe(&self) -> Receiver<ProjectionEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Delivers `event` to every subscriber still listening.
+    pub fn publish(&self, event: ProjectionEvent) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|tx| tx.send(event.clone()).is_ok());
+    }
+}
@@ -1,21 +1,43 @@
 use chrono::{TimeDelta, Utc};
-use colored::{ColoredString, Colorize};
+use colored::Colorize;
 use depack::DepackedMessage;
 use message::{LiveMessage, RawMessageDeserializeError};
 use simple_logger::SimpleLogger;
 use websocket::{ws::dataframe::DataFrame, Message, WebSocketError};
-use std::{env, io::ErrorKind, thread::sleep, time::Duration};
+use std::{
+    env,
+    io::ErrorKind,
+    sync::{atomic::{AtomicBool, Ordering}, Arc},
+    thread,
+    thread::sleep,
+    time::Duration,
+};
 
 mod config;
 mod depack;
+mod events;
+mod metrics;
 mod packet;
 mod message;
+mod projection;
+mod storage;
 
 use packet::{http::*, ws::*};
 use config::Config;
+use events::{colored_badge_name, colored_name, EventBus, ProjectionEvent};
+use metrics::Metrics;
+use storage::Storage;
 
 use crate::{depack::depack_packets, message::{guard::GuardLevel, interact::InteractType}};
 
+/// Backoff applied between reconnect attempts, reset to `INITIAL_RECONNECT_BACKOFF`
+/// once a connection survives past its first successful heartbeat response.
+const INITIAL_RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(30);
+/// Number of times the full `host_list` can be cycled through before we
+/// re-fetch `getDanmuInfo`, since the token expires eventually.
+const HOST_LIST_CYCLES_BEFORE_REFRESH: u32 = 3;
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     SimpleLogger::new().with_level(log::LevelFilter::Info).env().with_timestamp_format(
         time::macros::format_description!("[hour]:[minute]:[second]")
@@ -23,6 +45,19 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Get arguments
     let config = Config::from_args(env::args().collect());
 
+    // `--since` puts us in history-replay mode: read the archive and exit,
+    // skipping the network entirely.
+    if let Some(since) = config.replay_since {
+        let archive_path = config.archive_path.as_deref().unwrap_or("danmaku.sqlite3");
+        let storage = Storage::open(archive_path)?;
+        return replay_history(&storage, config.room_id, since, config.replay_limit);
+    }
+
+    let storage = match config.archive_path.as_deref() {
+        Some(path) => Some(Storage::open(path)?),
+        None => None,
+    };
+
     // Start calling APIs
     let agent = ureq::builder().tls_connector(native_tls::TlsConnector::new().unwrap().into()).build();
     // Get room data for the real room id
@@ -42,53 +77,186 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         "Requested real room ID: {}", room_id.to_string().bright_green()
     );
     // Get danmaku info data
-    let danmaku_info_data: DanmakuInfoData = agent.get(
-            &format!("https://api.live.bilibili.com/xlive/web-room/v1/index/getDanmuInfo?id={}", room_id)
-    )
-        .set("Cookie", format!("SESSDATA={}", config.sessdata.unwrap_or_default()).as_str())
-        .call()
-        .expect("Failed to request for room_init data")
-        .into_json::<HttpAPIResponse<DanmakuInfoData>>()
-        .expect("Failed to parse danmaku_info json data")
-        .response_data()
-        .expect("Response data is empty");
-
+    let sessdata = config.sessdata.clone().unwrap_or_default();
+    let mut danmaku_info_data = fetch_danmaku_info(&agent, room_id, &sessdata)?;
     log::info!(
         target: "main",
         "Requested token and WebSocket servers. {} servers available",
         danmaku_info_data.host_list.len().to_string().bright_green()
     );
 
-    // Get token and host uri
-    let token = danmaku_info_data.token;
-    let host = danmaku_info_data.host_list.get(0).expect("No available server in the list!").clone();
-    let host_url = format!("wss://{}:{}/sub", host.host, host.wss_port);
-    log::info!(
-        target: "main",
-        "Initializing connection to {} ...",
-        host_url.bright_green()
-    );
-    
+    let events = Arc::new(EventBus::new());
+    if let Some(irc_addr) = config.irc_addr.clone() {
+        let irc_events = events.subscribe();
+        thread::spawn(move || {
+            if let Err(e) = projection::irc::serve(&irc_addr, room_id, irc_events) {
+                log::warn!(target: "irc", "IRC projection stopped: {}", e);
+            }
+        });
+    }
+    if let Some(serve_addr) = config.serve_addr.clone() {
+        let overlay_events = events.clone();
+        thread::spawn(move || {
+            if let Err(e) = projection::http::serve(&serve_addr, overlay_events) {
+                log::warn!(target: "overlay", "Overlay server stopped: {}", e);
+            }
+        });
+    }
+
+    let metrics = Arc::new(Metrics::new());
+    if let Some(metrics_addr) = config.metrics_addr.clone() {
+        let metrics = metrics.clone();
+        thread::spawn(move || {
+            if let Err(e) = metrics::serve(&metrics_addr, metrics) {
+                log::warn!(target: "metrics", "Metrics server stopped: {}", e);
+            }
+        });
+    }
+
+    // Set on SIGINT so `start_listening` can send a WebSocket Close frame
+    // and the reconnect loop below can exit with code 0 instead of the
+    // process being killed mid-frame.
+    let shutdown = Arc::new(AtomicBool::new(false));
+    {
+        let shutdown = shutdown.clone();
+        ctrlc::set_handler(move || {
+            log::info!(target: "main", "Received Ctrl-C, shutting down gracefully...");
+            shutdown.store(true, Ordering::Relaxed);
+        })?;
+    }
+
+    // Connection manager: round-robins over `host_list` on every failure,
+    // backing off exponentially (with jitter) between attempts, and
+    // re-fetches the token/host list once the list has been fully
+    // cycled through `HOST_LIST_CYCLES_BEFORE_REFRESH` times. The cycle
+    // count is kept across successful connections and only reset by a
+    // refresh.
+    let mut host_index = 0usize;
+    let mut cycles_exhausted = 0u32;
+    let mut backoff = INITIAL_RECONNECT_BACKOFF;
+    let mut is_reconnect = false;
+
     loop {
-        if let Err(e) = start_listening(room_id, config.uid.unwrap_or(0), &token, &host_url) {
-            log::warn!(target: "init", "Error occured in the connection: \n {}", e.to_string());
-        } else {
-            log::warn!(target: "init", "Connection closed by server");
+        if danmaku_info_data.host_list.is_empty() {
+            log::warn!(target: "init", "No available server in the list, re-fetching...");
+            danmaku_info_data = fetch_danmaku_info(&agent, room_id, &sessdata)?;
+            host_index = 0;
+            cycles_exhausted = 0;
+        }
+
+        let host = danmaku_info_data.host_list[host_index].clone();
+        let host_url = format!("wss://{}:{}/sub", host.host, host.wss_port);
+        log::info!(target: "init", "Connecting to {} ...", host_url.bright_green());
+
+        if is_reconnect {
+            metrics.inc_reconnects();
+        }
+        is_reconnect = true;
+
+        let heartbeat_confirmed = AtomicBool::new(false);
+        let result = start_listening(
+            room_id,
+            config.uid.unwrap_or(0),
+            &danmaku_info_data.token,
+            &host_url,
+            &events,
+            storage.as_ref(),
+            &metrics,
+            &heartbeat_confirmed,
+            &shutdown,
+        );
+        match result {
+            Ok(()) => log::warn!(target: "init", "Connection closed by server"),
+            Err(e) => log::warn!(target: "init", "Error occured in the connection: \n {}", e.to_string()),
+        }
+
+        if shutdown.load(Ordering::Relaxed) {
+            // `storage` (if any) is dropped here, which closes the SQLite
+            // connection; every write up to this point was already
+            // committed synchronously by `Storage::record`.
+            log::info!(target: "main", "Shut down cleanly");
+            return Ok(());
+        }
+
+        if heartbeat_confirmed.load(Ordering::Relaxed) {
+            backoff = INITIAL_RECONNECT_BACKOFF;
         }
-        log::warn!(target: "init", "Trying to reconnect after 5 seconds");
-        sleep(Duration::from_secs(5));
+
+        host_index += 1;
+        if host_index >= danmaku_info_data.host_list.len() {
+            host_index = 0;
+            cycles_exhausted += 1;
+            if cycles_exhausted >= HOST_LIST_CYCLES_BEFORE_REFRESH {
+                log::info!(target: "init", "Exhausted host list {} times, refreshing token", cycles_exhausted);
+                match fetch_danmaku_info(&agent, room_id, &sessdata) {
+                    Ok(refreshed) => danmaku_info_data = refreshed,
+                    Err(e) => log::warn!(target: "init", "Failed to refresh danmaku info: {}", e),
+                }
+                cycles_exhausted = 0;
+            }
+        }
+
+        // A little jitter avoids every instance of the client retrying in lockstep.
+        let jitter = Duration::from_millis((Utc::now().timestamp_subsec_millis() % 500) as u64);
+        log::warn!(target: "init", "Trying to reconnect after {:?}", backoff);
+        interruptible_sleep(backoff + jitter, &shutdown);
+        if shutdown.load(Ordering::Relaxed) {
+            log::info!(target: "main", "Shut down cleanly");
+            return Ok(());
+        }
+        backoff = (backoff * 2).min(MAX_RECONNECT_BACKOFF);
     }
 }
 
+/// Sleeps for `duration`, but wakes up early (in at most `SHUTDOWN_POLL_INTERVAL`
+/// increments) if `shutdown` is set, so a Ctrl-C during the reconnect backoff
+/// doesn't add up to ~30s to how long the process takes to exit.
+fn interruptible_sleep(duration: Duration, shutdown: &AtomicBool) {
+    const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(200);
+    let mut remaining = duration;
+    while remaining > Duration::ZERO {
+        if shutdown.load(Ordering::Relaxed) {
+            return;
+        }
+        let step = remaining.min(SHUTDOWN_POLL_INTERVAL);
+        sleep(step);
+        remaining -= step;
+    }
+}
+
+/// Fetches a fresh token and WebSocket host list for `room_id`.
+fn fetch_danmaku_info(
+    agent: &ureq::Agent,
+    room_id: u64,
+    sessdata: &str,
+) -> Result<DanmakuInfoData, Box<dyn std::error::Error>> {
+    let danmaku_info_data: DanmakuInfoData = agent
+        .get(&format!(
+            "https://api.live.bilibili.com/xlive/web-room/v1/index/getDanmuInfo?id={}",
+            room_id
+        ))
+        .set("Cookie", format!("SESSDATA={}", sessdata).as_str())
+        .call()?
+        .into_json::<HttpAPIResponse<DanmakuInfoData>>()?
+        .response_data()
+        .ok_or("Response data is empty")?;
+    Ok(danmaku_info_data)
+}
+
 fn start_listening(
     room_id: u64,
     uid: u64,
     token: &str,
-    host_url: &str
+    host_url: &str,
+    events: &EventBus,
+    storage: Option<&Storage>,
+    metrics: &Metrics,
+    heartbeat_confirmed: &AtomicBool,
+    shutdown: &AtomicBool,
 ) -> Result<(), Box<dyn std::error::Error>> {
 
-    let mut client = websocket::ClientBuilder::new(host_url).unwrap()
-        .connect_secure(None).unwrap();
+    let mut client = websocket::ClientBuilder::new(host_url)?
+        .connect_secure(None)?;
     // Client should work in nonblocking mode
     client.set_nonblocking(true)?;
     log::info!(target: "client", "Successfully connected to server");
@@ -97,9 +265,18 @@ fn start_listening(
     // Send certificate
     client.send_message(&Message::binary(certificate_packet(uid, room_id, token)?))?;
     log::debug!(target: "client", "Certificate packet sent");
+    metrics.set_connected(true);
     // Main loop
 
     'main: loop {
+        if shutdown.load(Ordering::Relaxed) {
+            log::info!(target: "client", "Sending Close frame and disconnecting");
+            if let Err(e) = client.send_message(&Message::close(None)) {
+                log::warn!(target: "client", "Failed to send close frame: {}", e);
+            }
+            metrics.set_connected(false);
+            return Ok(());
+        }
         // Poll interval
         sleep(Duration::from_millis(200));
         // Check heartbeat
@@ -129,6 +306,7 @@ fn start_listening(
                 Err(e) => break 'poll e
             };
             if msg.is_close() {
+                metrics.set_connected(false);
                 return Ok(());
             }
             let data = msg.take_payload();
@@ -142,13 +320,17 @@ fn start_listening(
                 header
             );
             let message = match depack_packets(header, body) {
-                Ok(message) => message, 
+                Ok(message) => message,
                 Err(e) => {
                     log::debug!(target: "client", "Failed to depack packets: {}", e);
                     continue 'poll;
                 }
             };
-            process_depacked_message(message);
+            if matches!(message, DepackedMessage::HeartbeatResp(_)) {
+                heartbeat_confirmed.store(true, Ordering::Relaxed);
+                metrics.inc_heartbeats();
+            }
+            process_depacked_message(message, events, room_id, storage, metrics);
         };
         // Fetch out websocket errors
         let error = match error {
@@ -162,10 +344,15 @@ fn start_listening(
             },
             WebSocketError::NoDataAvailable => {
                 // Server disconnect
+                metrics.set_connected(false);
                 return Ok(());
             },
             e => e
         };
+        // This is a real (non-WouldBlock) error the loop is about to retry
+        // past; the connection isn't healthy, so the gauge shouldn't still
+        // claim it is.
+        metrics.set_connected(false);
         log::warn!(
             target: "client",
             "Error occured when trying to poll message from WebSocet: {}",
@@ -174,7 +361,28 @@ fn start_listening(
     }
 }
 
-fn process_depacked_message(message: DepackedMessage) {
+/// Prints what is archived for `room_id` since `since` (newest first, at
+/// most `limit` rows) through `storage::print_replayed`, a renderer separate
+/// from the live one in `process_live_message`.
+fn replay_history(
+    storage: &Storage,
+    room_id: u64,
+    since: chrono::DateTime<Utc>,
+    limit: Option<u64>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for (ts, event) in storage.replay(room_id, since, limit)? {
+        storage::print_replayed(ts, &event);
+    }
+    Ok(())
+}
+
+fn process_depacked_message(
+    message: DepackedMessage,
+    events: &EventBus,
+    room_id: u64,
+    storage: Option<&Storage>,
+    metrics: &Metrics,
+) {
     // Display certificate resp and heartbeat resp ony in debug
     let messages = match message {
         DepackedMessage::CertificateResp => {
@@ -199,45 +407,36 @@ fn process_depacked_message(message: DepackedMessage) {
                 continue;
             }
         };
-        process_live_message(live_message);
+        process_live_message(live_message, events, room_id, storage, metrics);
     }
 }
 
-fn process_live_message(message: LiveMessage) {
-
-    // Get colored name of a guard
-    fn get_colored_name(name: &str, guard_level: Option<GuardLevel>) -> ColoredString {
-        match guard_level {
-            None => name.bright_green(),
-            Some(GuardLevel::Captain) => name.bright_blue(),
-            Some(GuardLevel::Commander) => name.bright_purple(),
-            Some(GuardLevel::Governor) => name.bright_yellow(),
-        }
-    }
-
-    // Get colored badge message
-    fn get_colored_badge_name(name: &str, badge_level: u64) -> ColoredString {
-        match badge_level {
-            (1..=4)     => name.green(),
-            (5..=8)     => name.blue(),
-            (9..=12)    => name.magenta(),
-            (13..=16)   => name.red(),
-            (17..=20)   => name.yellow(),
-            (21..=24)   => name.bright_green(),
-            (25..=28)   => name.bright_blue(),
-            (29..=32)   => name.bright_magenta(),
-            (33..=36)   => name.bright_red(),
-            (37..=40)   => name.bright_yellow(),
-            _           => name.clear(),
+fn process_live_message(
+    message: LiveMessage,
+    events: &EventBus,
+    room_id: u64,
+    storage: Option<&Storage>,
+    metrics: &Metrics,
+) {
+    // Fans `event` out to live subscribers (IRC, overlay) and, if an
+    // archive is configured, persists it with the current timestamp.
+    let publish = |event: ProjectionEvent| {
+        if let Some(storage) = storage {
+            if let Err(e) = storage.record(room_id, &event) {
+                log::warn!(target: "storage", "Failed to archive message: {}", e);
+            }
         }
-    }
+        events.publish(event);
+    };
 
     match message {
         LiveMessage::LiveStart(_) => {
             println!(" * {}", "直播开始了".bright_green());
+            publish(ProjectionEvent::LiveStart);
         }
         LiveMessage::LiveStop(_) => {
             println!(" * {}", "直播结束了".bright_red());
+            publish(ProjectionEvent::LiveStop);
         }
         LiveMessage::Welcome(info) => {
             let username = match info.is_admin {
@@ -247,7 +446,7 @@ fn process_live_message(message: LiveMessage) {
             println!(" * {} 进入了直播间", username);
         }
         LiveMessage::WelcomeGuard(info) => {
-            println!(" * {} 进入了直播间", get_colored_name(&info.username, info.guard_level));
+            println!(" * {} 进入了直播间", colored_name(&info.username, info.guard_level));
         }
         LiveMessage::Warning(info) => {
             println!(" * {} {}", "超管警告".bright_red(), info.message.bright_red())
@@ -258,11 +457,11 @@ fn process_live_message(message: LiveMessage) {
         LiveMessage::Danmaku(info) => {
             let username = match (info.is_admin, info.guard_level) {
                 (true, _) => info.username.bright_red(),
-                (false, level) => get_colored_name(&info.username, level)
+                (false, level) => colored_name(&info.username, level)
             };
             let badge_text = match info.badge {
                 Some(badge) => {
-                    format!("[{} {}] ", get_colored_badge_name(&badge.badge_name, badge.level), badge.level)
+                    format!("[{} {}] ", colored_badge_name(&badge.badge_name, badge.level), badge.level)
                 }
                 None => "".to_string()
             };
@@ -272,6 +471,15 @@ fn process_live_message(message: LiveMessage) {
                 username,
                 info.text
             );
+            publish(ProjectionEvent::Danmaku {
+                uid: info.uid,
+                username: info.username.clone(),
+                guard_level: info.guard_level,
+                badge_name: info.badge.as_ref().map(|badge| badge.badge_name.clone()),
+                badge_level: info.badge.as_ref().map(|badge| badge.level),
+                text: info.text.clone(),
+            });
+            metrics.record_danmaku();
         }
         LiveMessage::SendGift(info) => {
             println!(
@@ -280,6 +488,13 @@ fn process_live_message(message: LiveMessage) {
                 info.count.to_string().bright_yellow(),
                 info.gift_name.bright_magenta(),
             );
+            publish(ProjectionEvent::SendGift {
+                uid: info.uid,
+                username: info.username.clone(),
+                gift_name: info.gift_name.clone(),
+                count: info.count,
+            });
+            metrics.record_gift(&info.gift_name, info.count);
         }
         LiveMessage::SuperChat(info) => {
             println!(
@@ -287,7 +502,14 @@ fn process_live_message(message: LiveMessage) {
                 format!("$ {:.2}", info.price).bright_yellow(),
                 info.username.bright_green(),
                 info.message.bright_yellow(),
-            )
+            );
+            publish(ProjectionEvent::SuperChat {
+                uid: info.uid,
+                username: info.username.clone(),
+                price: info.price,
+                message: info.message.clone(),
+            });
+            metrics.record_superchat(info.price);
         }
         LiveMessage::Interact(info) => {
             match info.interact_type {
@@ -307,6 +529,12 @@ fn process_live_message(message: LiveMessage) {
                     println!(" * {} 互关了你", info.username.bright_green())
                 }
             }
+            publish(ProjectionEvent::Interact {
+                uid: info.uid,
+                username: info.username.clone(),
+                interact_type: info.interact_type,
+            });
+            metrics.record_interact(info.interact_type);
         }
         LiveMessage::GuardBuy(info) => {
             let guard_name = match info.guard_level {
@@ -316,10 +544,17 @@ fn process_live_message(message: LiveMessage) {
             };
             println!(
                 " * {} 成为了 {} ({} 个月)",
-                get_colored_name(&info.username, Some(info.guard_level)),
-                get_colored_name(guard_name, Some(info.guard_level)),
+                colored_name(&info.username, Some(info.guard_level)),
+                colored_name(guard_name, Some(info.guard_level)),
                 info.count.to_string().bright_yellow()
             );
+            publish(ProjectionEvent::GuardBuy {
+                uid: info.uid,
+                username: info.username.clone(),
+                guard_level: info.guard_level,
+                count: info.count,
+            });
+            metrics.record_guard_buy(info.guard_level);
         }
         #[allow(unreachable_patterns)]
         other => {
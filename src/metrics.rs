@@ -0,0 +1,195 @@
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    io::Write,
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use crate::message::{guard::GuardLevel, interact::InteractType};
+
+/// Upper bounds (in bilibili-coin yuan) of the superchat price histogram buckets.
+const SUPERCHAT_PRICE_BUCKETS: &[f64] = &[1.0, 5.0, 10.0, 30.0, 50.0, 100.0, 200.0, 500.0];
+
+/// Escapes `"`, `\` and newlines out of a label value per the Prometheus
+/// text exposition format, so a gift name (or any other catalog/user-
+/// controlled text) can't break the label's quoting and corrupt the rest
+/// of the scrape.
+fn escape_label(value: &str) -> String {
+    value.chars().fold(String::with_capacity(value.len()), |mut out, c| {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            _ => out.push(c),
+        }
+        out
+    })
+}
+
+/// Prometheus counters/gauges tracking a room's activity, exposed as plain
+/// text on `/metrics` so Grafana (or anything else that scrapes Prometheus
+/// exposition format) can graph engagement and alert on disconnects.
+pub struct Metrics {
+    danmaku_total: AtomicU64,
+    gift_total: Mutex<HashMap<String, (u64, u64)>>,
+    superchat_total: AtomicU64,
+    superchat_price_buckets: Mutex<Vec<u64>>,
+    superchat_price_sum: Mutex<f64>,
+    guard_buy_total: Mutex<HashMap<&'static str, u64>>,
+    interact_total: Mutex<HashMap<&'static str, u64>>,
+    connected: AtomicBool,
+    reconnects_total: AtomicU64,
+    heartbeats_total: AtomicU64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            danmaku_total: AtomicU64::new(0),
+            gift_total: Mutex::new(HashMap::new()),
+            superchat_total: AtomicU64::new(0),
+            superchat_price_buckets: Mutex::new(vec![0; SUPERCHAT_PRICE_BUCKETS.len() + 1]),
+            superchat_price_sum: Mutex::new(0.0),
+            guard_buy_total: Mutex::new(HashMap::new()),
+            interact_total: Mutex::new(HashMap::new()),
+            connected: AtomicBool::new(false),
+            reconnects_total: AtomicU64::new(0),
+            heartbeats_total: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record_danmaku(&self) {
+        self.danmaku_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_gift(&self, gift_name: &str, count: u64) {
+        let mut totals = self.gift_total.lock().unwrap();
+        let entry = totals.entry(gift_name.to_string()).or_insert((0, 0));
+        entry.0 += 1;
+        entry.1 += count;
+    }
+
+    pub fn record_superchat(&self, price: f64) {
+        self.superchat_total.fetch_add(1, Ordering::Relaxed);
+        *self.superchat_price_sum.lock().unwrap() += price;
+        let bucket = SUPERCHAT_PRICE_BUCKETS
+            .iter()
+            .position(|&bound| price <= bound)
+            .unwrap_or(SUPERCHAT_PRICE_BUCKETS.len());
+        self.superchat_price_buckets.lock().unwrap()[bucket] += 1;
+    }
+
+    pub fn record_guard_buy(&self, guard_level: GuardLevel) {
+        let label = match guard_level {
+            GuardLevel::Captain => "captain",
+            GuardLevel::Commander => "commander",
+            GuardLevel::Governor => "governor",
+        };
+        *self.guard_buy_total.lock().unwrap().entry(label).or_insert(0) += 1;
+    }
+
+    pub fn record_interact(&self, interact_type: InteractType) {
+        let label = match interact_type {
+            InteractType::Enter => "enter",
+            InteractType::Follow => "follow",
+            InteractType::Share => "share",
+            InteractType::SpecialFollow => "special_follow",
+            InteractType::MutualFollow => "mutual_follow",
+        };
+        *self.interact_total.lock().unwrap().entry(label).or_insert(0) += 1;
+    }
+
+    pub fn set_connected(&self, connected: bool) {
+        self.connected.store(connected, Ordering::Relaxed);
+    }
+
+    pub fn inc_reconnects(&self) {
+        self.reconnects_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_heartbeats(&self) {
+        self.heartbeats_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Renders every metric in Prometheus text exposition format.
+    fn render(&self) -> String {
+        let mut out = String::new();
+
+        let _ = writeln!(out, "# TYPE danmaku_total counter");
+        let _ = writeln!(out, "danmaku_total {}", self.danmaku_total.load(Ordering::Relaxed));
+
+        let _ = writeln!(out, "# TYPE gift_total counter");
+        let _ = writeln!(out, "# TYPE gift_count_total counter");
+        for (gift_name, (sends, count)) in self.gift_total.lock().unwrap().iter() {
+            let gift_name = escape_label(gift_name);
+            let _ = writeln!(out, "gift_total{{gift_name=\"{gift_name}\"}} {sends}");
+            let _ = writeln!(out, "gift_count_total{{gift_name=\"{gift_name}\"}} {count}");
+        }
+
+        let _ = writeln!(out, "# TYPE superchat_total counter");
+        let _ = writeln!(out, "superchat_total {}", self.superchat_total.load(Ordering::Relaxed));
+
+        let _ = writeln!(out, "# TYPE superchat_price histogram");
+        let buckets = self.superchat_price_buckets.lock().unwrap();
+        let mut cumulative = 0u64;
+        for (bound, count) in SUPERCHAT_PRICE_BUCKETS.iter().zip(buckets.iter()) {
+            cumulative += count;
+            let _ = writeln!(out, "superchat_price_bucket{{le=\"{bound}\"}} {cumulative}");
+        }
+        cumulative += buckets[SUPERCHAT_PRICE_BUCKETS.len()];
+        let _ = writeln!(out, "superchat_price_bucket{{le=\"+Inf\"}} {cumulative}");
+        let _ = writeln!(out, "superchat_price_sum {}", *self.superchat_price_sum.lock().unwrap());
+        let _ = writeln!(out, "superchat_price_count {cumulative}");
+
+        let _ = writeln!(out, "# TYPE guard_buy_total counter");
+        for (guard_level, count) in self.guard_buy_total.lock().unwrap().iter() {
+            let _ = writeln!(out, "guard_buy_total{{guard_level=\"{guard_level}\"}} {count}");
+        }
+
+        let _ = writeln!(out, "# TYPE interact_total counter");
+        for (interact_type, count) in self.interact_total.lock().unwrap().iter() {
+            let _ = writeln!(out, "interact_total{{interact_type=\"{interact_type}\"}} {count}");
+        }
+
+        let _ = writeln!(out, "# TYPE connected gauge");
+        let _ = writeln!(out, "connected {}", self.connected.load(Ordering::Relaxed) as u8);
+
+        let _ = writeln!(out, "# TYPE reconnects_total counter");
+        let _ = writeln!(out, "reconnects_total {}", self.reconnects_total.load(Ordering::Relaxed));
+
+        let _ = writeln!(out, "# TYPE heartbeats_total counter");
+        let _ = writeln!(out, "heartbeats_total {}", self.heartbeats_total.load(Ordering::Relaxed));
+
+        out
+    }
+}
+
+/// Serves `/metrics` on `addr` in Prometheus text exposition format.
+pub fn serve(addr: &str, metrics: std::sync::Arc<Metrics>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    log::info!(target: "metrics", "Metrics endpoint listening on http://{}/metrics", addr);
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        let metrics = metrics.clone();
+        thread::spawn(move || handle_connection(stream, &metrics));
+    }
+
+    Ok(())
+}
+
+fn handle_connection(mut stream: TcpStream, metrics: &Metrics) {
+    // The request itself is irrelevant: this endpoint only ever serves `/metrics`.
+    let body = metrics.render();
+    let _ = write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    );
+}
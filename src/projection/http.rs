@@ -0,0 +1,96 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use crate::events::EventBus;
+
+const OVERLAY_HTML: &str = include_str!("overlay.html");
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Serves the OBS browser-source overlay on `addr`: `GET /` returns the
+/// bundled `overlay.html`, and `GET /events` streams every `ProjectionEvent`
+/// as `text/event-stream`. Every connection subscribes to `events`
+/// independently, so multiple overlays (or browser tabs) can be open at once.
+pub fn serve(addr: &str, events: Arc<EventBus>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    log::info!(target: "overlay", "Overlay server listening on http://{}", addr);
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        let events = events.clone();
+        thread::spawn(move || handle_connection(stream, &events));
+    }
+
+    Ok(())
+}
+
+fn handle_connection(mut stream: TcpStream, events: &EventBus) {
+    let mut reader = BufReader::new(stream.try_clone().expect("failed to clone stream"));
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+        return;
+    }
+    // Drain the rest of the request headers; we don't need them. The header
+    // block ends at the first empty line (just "\r\n"), so compare the
+    // trimmed line rather than the byte count read.
+    let mut header_line = String::new();
+    loop {
+        header_line.clear();
+        if reader.read_line(&mut header_line).unwrap_or(0) == 0 {
+            break;
+        }
+        if header_line.trim_end_matches(['\r', '\n']).is_empty() {
+            break;
+        }
+    }
+
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+    match path {
+        "/events" => serve_events(stream, events),
+        _ => serve_overlay(&mut stream),
+    }
+}
+
+fn serve_overlay(stream: &mut TcpStream) {
+    let _ = write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        OVERLAY_HTML.len(),
+        OVERLAY_HTML
+    );
+}
+
+fn serve_events(mut stream: TcpStream, events: &EventBus) {
+    if write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n"
+    ).is_err() {
+        return;
+    }
+
+    let subscription = events.subscribe();
+    loop {
+        match subscription.recv_timeout(KEEPALIVE_INTERVAL) {
+            Ok(event) => {
+                let json = match serde_json::to_string(&event) {
+                    Ok(json) => json,
+                    Err(_) => continue,
+                };
+                if write!(stream, "data: {}\n\n", json).is_err() {
+                    return;
+                }
+            }
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                // Proxies drop idle streams; a comment line keeps the connection alive.
+                if write!(stream, ":keepalive\n\n").is_err() {
+                    return;
+                }
+            }
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    events::ProjectionEvent,
+    message::{guard::GuardLevel, interact::InteractType},
+};
+
+/// How long a single write to a connected IRC client may block before it's
+/// treated as stalled and the client is dropped.
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Runs the local IRC projection on `addr` (e.g. `127.0.0.1:6667`) and
+/// blocks forever, translating `events` into IRC protocol lines for every
+/// connected client. The room is exposed as a single read-only channel,
+/// `#room<room_id>`.
+pub fn serve(addr: &str, room_id: u64, events: Receiver<ProjectionEvent>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    log::info!(target: "irc", "IRC projection listening on {}", addr);
+
+    let channel = Arc::new(format!("#room{}", room_id));
+    let clients: Arc<Mutex<Vec<Sender<String>>>> = Arc::new(Mutex::new(Vec::new()));
+
+    // Accept connections on their own thread; each client then gets a
+    // handler thread for inbound NICK/JOIN/PING traffic and a second
+    // writer thread with its own outbound queue, so a slow or stalled
+    // reader can only block its own writer thread, never the broadcast
+    // loop below or any other client.
+    {
+        let clients = clients.clone();
+        let channel = channel.clone();
+        let listener = listener.try_clone()?;
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                let Ok(mut writer) = stream.try_clone() else { continue };
+                if writer.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT)).is_err() {
+                    continue;
+                }
+                let (tx, rx) = mpsc::channel::<String>();
+                clients.lock().unwrap().push(tx);
+                thread::spawn(move || {
+                    for line in rx {
+                        if writer.write_all(line.as_bytes()).is_err() {
+                            break;
+                        }
+                    }
+                });
+                let channel = channel.clone();
+                thread::spawn(move || handle_client(stream, &channel));
+            }
+        });
+    }
+
+    for event in events {
+        let lines = render(&channel, &event);
+        if lines.is_empty() {
+            continue;
+        }
+        let batch = lines.concat();
+        let mut clients = clients.lock().unwrap();
+        clients.retain(|client| client.send(batch.clone()).is_ok());
+    }
+
+    Ok(())
+}
+
+/// Handles the handshake and keepalive traffic for a single IRC client.
+/// Outgoing `PRIVMSG`/`QUIT` is read and discarded since the room is read-only.
+fn handle_client(stream: TcpStream, channel: &str) {
+    let Ok(mut writer) = stream.try_clone() else { return };
+    let mut reader = BufReader::new(stream);
+    let mut nick = String::from("guest");
+    let mut line = String::new();
+
+    while reader.read_line(&mut line).unwrap_or(0) > 0 {
+        let trimmed = line.trim_end();
+        let mut parts = trimmed.splitn(2, ' ');
+        match parts.next().unwrap_or("") {
+            "NICK" => {
+                nick = parts.next().unwrap_or("guest").trim().to_string();
+            }
+            "USER" => {
+                let _ = write!(
+                    writer,
+                    ":bilibili-danmaku 001 {nick} :Welcome to the danmaku projection\r\n\
+                     :bilibili-danmaku 375 {nick} :- bilibili-danmaku-cli IRC bridge\r\n\
+                     :bilibili-danmaku 376 {nick} :End of /MOTD command\r\n"
+                );
+            }
+            "JOIN" => {
+                let _ = write!(
+                    writer,
+                    ":{nick}!{nick}@bilibili JOIN {channel}\r\n\
+                     :bilibili-danmaku 332 {nick} {channel} :Bilibili live danmaku\r\n\
+                     :bilibili-danmaku 353 {nick} = {channel} :{nick}\r\n\
+                     :bilibili-danmaku 366 {nick} {channel} :End of /NAMES list\r\n"
+                );
+            }
+            "PING" => {
+                let token = parts.next().unwrap_or("");
+                let _ = write!(writer, "PONG {token}\r\n");
+            }
+            _ => {
+                // PRIVMSG/QUIT/anything else: room is read-only, ignore.
+            }
+        }
+        line.clear();
+    }
+}
+
+/// Maps a guard level onto the IRC mode-prefix convention (`@`/`+`) so
+/// existing terminal coloring has a rough equivalent in IRC clients.
+fn nick_prefix(guard_level: Option<GuardLevel>) -> &'static str {
+    match guard_level {
+        Some(GuardLevel::Governor) | Some(GuardLevel::Commander) => "@",
+        Some(GuardLevel::Captain) => "+",
+        None => "",
+    }
+}
+
+/// Maximum length (in chars) of a user-controlled field once embedded in an
+/// IRC line, mirroring real IRC servers' line-length limits.
+const MAX_FIELD_LEN: usize = 400;
+
+/// Strips CR/LF from a user-controlled field before it's interpolated into
+/// an IRC line: an embedded `\r`/`\n` would otherwise let a viewer inject
+/// arbitrary extra protocol lines (e.g. a spoofed `PRIVMSG`) into every
+/// connected client's stream.
+fn sanitize(field: &str) -> String {
+    field.chars().filter(|c| *c != '\r' && *c != '\n').take(MAX_FIELD_LEN).collect()
+}
+
+fn render(channel: &str, event: &ProjectionEvent) -> Vec<String> {
+    match event {
+        ProjectionEvent::Danmaku { username, guard_level, badge_name, text, .. } => {
+            let username = sanitize(username);
+            let text = sanitize(text);
+            let prefix = nick_prefix(*guard_level);
+            let nick = match badge_name {
+                Some(badge) => format!("{prefix}{username}[{}]", sanitize(badge)),
+                None => format!("{prefix}{username}"),
+            };
+            vec![format!(":{nick}!{username}@bilibili PRIVMSG {channel} :{text}\r\n")]
+        }
+        ProjectionEvent::SendGift { username, gift_name, count } => {
+            let username = sanitize(username);
+            let gift_name = sanitize(gift_name);
+            vec![format!(
+                ":bilibili-danmaku NOTICE {channel} :{username} sent {count} x {gift_name}\r\n"
+            )]
+        }
+        ProjectionEvent::SuperChat { username, price, message } => {
+            let username = sanitize(username);
+            let message = sanitize(message);
+            vec![format!(
+                ":bilibili-danmaku NOTICE {channel} :[SC ${price:.2}] {username}: {message}\r\n"
+            )]
+        }
+        ProjectionEvent::GuardBuy { username, guard_level, count } => {
+            let username = sanitize(username);
+            vec![format!(
+                ":bilibili-danmaku NOTICE {channel} :{username} bought guard level {guard_level:?} x{count}\r\n"
+            )]
+        }
+        ProjectionEvent::Interact { username, interact_type } => {
+            let username = sanitize(username);
+            let verb = match interact_type {
+                InteractType::Enter => "entered the room",
+                InteractType::Follow => "followed",
+                InteractType::Share => "shared the room",
+                InteractType::SpecialFollow => "specially followed",
+                InteractType::MutualFollow => "mutually followed",
+            };
+            vec![format!(":bilibili-danmaku NOTICE {channel} :{username} {verb}\r\n")]
+        }
+        ProjectionEvent::LiveStart => {
+            vec![format!(":bilibili-danmaku TOPIC {channel} :Live started\r\n")]
+        }
+        ProjectionEvent::LiveStop => {
+            vec![format!(":bilibili-danmaku TOPIC {channel} :Live ended\r\n")]
+        }
+    }
+}
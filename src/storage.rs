@@ -0,0 +1,167 @@
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use rusqlite::{params, Connection};
+
+use crate::events::{colored_badge_name, colored_name, ProjectionEvent};
+
+/// Durable archive of the danmaku, gifts, superchats, guard purchases,
+/// interactions and live start/stop events the client has displayed for a
+/// room. Welcome, warning and cut-off notices are not archived.
+/// Opens (and migrates) a SQLite database file. The WebSocket frames don't
+/// always carry a reliable send time, so every row is stamped with
+/// `Utc::now()` at ingestion, mirroring how a chat server timestamps
+/// messages on receipt rather than trusting the client's clock.
+pub struct Storage {
+    connection: Connection,
+}
+
+impl Storage {
+    pub fn open(path: &str) -> rusqlite::Result<Storage> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS messages (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                room_id INTEGER NOT NULL,
+                ts TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                username TEXT,
+                payload TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_messages_room_ts ON messages (room_id, ts);",
+        )?;
+        // Migrate archives created before the `uid` column existed; SQLite
+        // errors if the column is already there, which we can ignore.
+        let _ = connection.execute("ALTER TABLE messages ADD COLUMN uid INTEGER", []);
+        Ok(Storage { connection })
+    }
+
+    /// Records `event` for `room_id`, stamped with the current time.
+    pub fn record(&self, room_id: u64, event: &ProjectionEvent) -> rusqlite::Result<()> {
+        let payload = serde_json::to_string(event).map_err(|e| {
+            rusqlite::Error::ToSqlConversionFailure(Box::new(e))
+        })?;
+        self.connection.execute(
+            "INSERT INTO messages (room_id, ts, kind, username, uid, payload) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![room_id, Utc::now().to_rfc3339(), kind_of(event), username_of(event), uid_of(event), payload],
+        )?;
+        Ok(())
+    }
+
+    /// Returns up to `limit` rows for `room_id` at or after `since`, newest first.
+    pub fn replay(
+        &self,
+        room_id: u64,
+        since: DateTime<Utc>,
+        limit: Option<u64>,
+    ) -> rusqlite::Result<Vec<(DateTime<Utc>, ProjectionEvent)>> {
+        let mut statement = self.connection.prepare(
+            "SELECT ts, payload FROM messages
+             WHERE room_id = ?1 AND ts >= ?2
+             ORDER BY ts DESC
+             LIMIT ?3",
+        )?;
+        // `i64::MAX` stands in for "no limit" when `limit` is `None` — an
+        // explicit, already-huge row count, rather than relying on SQLite's
+        // negative-`LIMIT`-means-unlimited convention via a `u64::MAX`
+        // bit-reinterpretation, which reads like an overflow bug.
+        let row_limit = limit.map(|limit| limit as i64).unwrap_or(i64::MAX);
+        let rows = statement.query_map(
+            params![room_id, since.to_rfc3339(), row_limit],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        )?;
+
+        let mut out = Vec::new();
+        for row in rows {
+            let (ts, payload) = row?;
+            let Ok(ts) = DateTime::parse_from_rfc3339(&ts) else { continue };
+            let Ok(event) = serde_json::from_str::<ProjectionEvent>(&payload) else { continue };
+            out.push((ts.with_timezone(&Utc), event));
+        }
+        Ok(out)
+    }
+}
+
+fn kind_of(event: &ProjectionEvent) -> &'static str {
+    match event {
+        ProjectionEvent::Danmaku { .. } => "danmaku",
+        ProjectionEvent::SendGift { .. } => "gift",
+        ProjectionEvent::SuperChat { .. } => "superchat",
+        ProjectionEvent::GuardBuy { .. } => "guard",
+        ProjectionEvent::Interact { .. } => "interact",
+        ProjectionEvent::LiveStart => "live_start",
+        ProjectionEvent::LiveStop => "live_stop",
+    }
+}
+
+fn username_of(event: &ProjectionEvent) -> Option<&str> {
+    match event {
+        ProjectionEvent::Danmaku { username, .. }
+        | ProjectionEvent::SendGift { username, .. }
+        | ProjectionEvent::SuperChat { username, .. }
+        | ProjectionEvent::GuardBuy { username, .. }
+        | ProjectionEvent::Interact { username, .. } => Some(username),
+        ProjectionEvent::LiveStart | ProjectionEvent::LiveStop => None,
+    }
+}
+
+/// Sender uid, so two viewers sharing a display name stay distinguishable
+/// in the archive and replay view.
+fn uid_of(event: &ProjectionEvent) -> Option<u64> {
+    match event {
+        ProjectionEvent::Danmaku { uid, .. }
+        | ProjectionEvent::SendGift { uid, .. }
+        | ProjectionEvent::SuperChat { uid, .. }
+        | ProjectionEvent::GuardBuy { uid, .. }
+        | ProjectionEvent::Interact { uid, .. } => Some(*uid),
+        ProjectionEvent::LiveStart | ProjectionEvent::LiveStop => None,
+    }
+}
+
+/// Prints an archived event, prefixed with the timestamp it was recorded at.
+/// Danmaku, gift, superchat and live start/stop lines use the live
+/// renderer's layout and colors, except that admins are not shown in red
+/// (the archive does not keep that flag). Guard purchases and interactions
+/// print the guard level or interaction type by its variant name, and the
+/// guard buyer's name is not colored by guard level.
+pub fn print_replayed(ts: DateTime<Utc>, event: &ProjectionEvent) {
+    let timestamp = ts.format("%Y-%m-%d %H:%M:%S").to_string().bright_black();
+    match event {
+        ProjectionEvent::Danmaku { username, guard_level, badge_name, badge_level, text, .. } => {
+            let username = colored_name(username, *guard_level);
+            let badge_text = match (badge_name, badge_level) {
+                (Some(name), Some(level)) => format!("[{} {}] ", colored_badge_name(name, *level), level),
+                _ => "".to_string(),
+            };
+            println!("[{timestamp}] {}{}\n : {}", badge_text, username, text);
+        }
+        ProjectionEvent::SendGift { username, gift_name, count } => {
+            println!(
+                "[{timestamp}] * {} 投喂了 {} 个 {}",
+                username.bright_green(),
+                count.to_string().bright_yellow(),
+                gift_name.bright_magenta(),
+            );
+        }
+        ProjectionEvent::SuperChat { username, price, message } => {
+            println!(
+                "[{timestamp}] ({}) <{}> {}",
+                format!("$ {price:.2}").bright_yellow(),
+                username.bright_green(),
+                message.bright_yellow(),
+            );
+        }
+        ProjectionEvent::GuardBuy { username, guard_level, count } => {
+            println!(
+                "[{timestamp}] * {} 成为了舰队成员 {:?} ({} 个月)",
+                username.bright_green(),
+                guard_level,
+                count.to_string().bright_yellow(),
+            );
+        }
+        ProjectionEvent::Interact { username, interact_type } => {
+            println!("[{timestamp}] * {} {:?}", username.bright_green(), interact_type);
+        }
+        ProjectionEvent::LiveStart => println!("[{timestamp}] * {}", "直播开始了".bright_green()),
+        ProjectionEvent::LiveStop => println!("[{timestamp}] * {}", "直播结束了".bright_red()),
+    }
+}